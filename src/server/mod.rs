@@ -13,12 +13,20 @@ use crate::{
 use config::*;
 use request::*;
 
-use futures::FutureExt as _;
-use std::{collections::HashMap, sync::Arc};
+use futures::{future, FutureExt as _};
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{
+        atomic::{AtomicU32, AtomicU64, Ordering},
+        Arc,
+    },
+};
 use tokio::{
+    io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
     sync::{
         broadcast::{self, error::RecvError},
-        mpsc, Mutex,
+        mpsc, watch, Mutex,
     },
     time::{Duration, Instant},
 };
@@ -26,6 +34,497 @@ use util::Conn;
 
 const INBOUND_MTU: usize = 1500;
 
+/// Initial delay before the first restart attempt of a crashed read loop.
+const MIN_LISTENER_RESTART_BACKOFF: Duration = Duration::from_millis(100);
+/// Upper bound the restart backoff is capped at, no matter how many
+/// consecutive restarts a listener has needed.
+const MAX_LISTENER_RESTART_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Doubles `current`, capped at [`MAX_LISTENER_RESTART_BACKOFF`], for the
+/// next restart attempt after one at `current` has failed.
+fn next_backoff(current: Duration) -> Duration {
+    (current * 2).min(MAX_LISTENER_RESTART_BACKOFF)
+}
+
+/// Identifies a listening connection's supervised read loop for the
+/// lifetime of the [`Server`].
+pub type ListenerId = u64;
+
+/// Point-in-time health snapshot of a supervised listener, returned by
+/// [`Server::listener_status`].
+#[derive(Debug, Clone)]
+pub struct ListenerStatus {
+    pub id: ListenerId,
+    /// Whether the read loop is currently running. `false` means it is
+    /// either permanently stopped or waiting out its restart backoff.
+    pub alive: bool,
+    /// How many times this listener's read loop has been restarted after
+    /// exiting on a transient error or panicking.
+    pub restart_count: u32,
+}
+
+/// Shared health state for one supervised read loop. The supervisor task
+/// updates it; [`Server::listener_status`] reads it without needing the
+/// read loop itself to be alive to answer.
+struct ListenerHealth {
+    id: ListenerId,
+    alive: std::sync::atomic::AtomicBool,
+    restart_count: AtomicU32,
+}
+
+/// Why [`Server::read_loop`] returned, so its supervisor can decide whether
+/// to restart it.
+enum ReadLoopExit {
+    /// Asked to stop via [`Command::Close`], a completed drain, or the
+    /// broadcast channel closing. The listener should not be restarted.
+    Closed,
+    /// Exited because `recv_from`/`accept` returned a transient error.
+    /// Worth restarting.
+    Error,
+    /// Exited because `recv_from`/`accept` returned an error that restarting
+    /// the same listener can't fix (e.g. a closed or misconfigured socket).
+    /// Retrying forever would just spin, so the listener is not restarted.
+    FatalError,
+}
+
+/// Returns `true` for I/O errors that restarting the listener won't fix —
+/// a socket that's not connected, an address that's unavailable or in use,
+/// a permission error, an invalid argument, an operation the socket
+/// doesn't support — as opposed to transient errors (e.g. a
+/// momentary resource shortage) that are worth retrying.
+///
+/// `conn.recv_from`/`listener.accept` errors may arrive wrapped in this
+/// crate's own [`Error`] (or `util::Error`) rather than a bare
+/// `std::io::Error`, so this walks the `source()` chain instead of trying a
+/// single downcast.
+fn is_fatal_io_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    use std::io::ErrorKind::*;
+
+    let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(err);
+    while let Some(err) = cause {
+        if let Some(io_err) = err.downcast_ref::<std::io::Error>() {
+            return matches!(
+                io_err.kind(),
+                NotConnected
+                    | AddrNotAvailable
+                    | AddrInUse
+                    | PermissionDenied
+                    | InvalidInput
+                    | Unsupported
+            );
+        }
+        cause = err.source();
+    }
+    false
+}
+
+/// STUN error code for "Allocation Quota Reached" (RFC 5766 section 6.2).
+const ERR_ALLOCATION_QUOTA_REACHED: u16 = 486;
+
+/// Per-source token-bucket limits. A [`Server`] keeps separate buckets for
+/// Allocate requests and general traffic, since amplification/exhaustion
+/// abuse is usually an Allocate flood while ordinary relayed traffic from an
+/// already-established allocation should rarely be throttled.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitConfig {
+    /// Sustained datagrams per second allowed per source for non-Allocate
+    /// traffic.
+    pub general_rate: f64,
+    /// Burst size (bucket capacity) for non-Allocate traffic.
+    pub general_burst: f64,
+    /// Sustained Allocate requests per second allowed per source.
+    pub allocate_rate: f64,
+    /// Burst size (bucket capacity) for Allocate requests.
+    pub allocate_burst: f64,
+    /// How long a source's bucket is kept around after its last refill
+    /// before being pruned to bound memory use.
+    pub bucket_ttl: Duration,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        RateLimitConfig {
+            general_rate: 50.0,
+            general_burst: 100.0,
+            allocate_rate: 2.0,
+            allocate_burst: 5.0,
+            bucket_ttl: Duration::from_secs(300),
+        }
+    }
+}
+
+/// Snapshot of how many packets a [`Server`]'s rate limiter has dropped,
+/// returned by [`Server::rate_limit_stats`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RateLimitStats {
+    pub throttled_general: u64,
+    pub throttled_allocate: u64,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(initial_tokens: f64) -> Self {
+        TokenBucket {
+            tokens: initial_tokens,
+            last_refill: Instant::now(),
+        }
+    }
+
+    /// Refills based on elapsed time, then consumes a token if one is
+    /// available. Returns `false` (without consuming a token; the refill
+    /// still applies) when the source must be throttled.
+    fn try_consume(&mut self, rate: f64, burst: f64) -> bool {
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(burst);
+        self.last_refill = now;
+
+        if self.tokens < 1.0 {
+            false
+        } else {
+            self.tokens -= 1.0;
+            true
+        }
+    }
+}
+
+/// A [`TokenBucket`] map plus the last time it was pruned, so pruning can be
+/// amortized across many calls to [`RateLimiter::allow`] instead of
+/// happening on every one of them.
+struct BucketTable {
+    buckets: HashMap<SocketAddr, TokenBucket>,
+    last_pruned: Instant,
+}
+
+impl BucketTable {
+    fn new() -> Self {
+        BucketTable {
+            buckets: HashMap::new(),
+            last_pruned: Instant::now(),
+        }
+    }
+}
+
+/// Per-source flood control shared by every listener on a [`Server`]. Keyed
+/// by the datagram's source [`SocketAddr`] rather than by connection, so a
+/// source abusing one listener can't just move to another to reset its
+/// budget.
+struct RateLimiter {
+    config: RateLimitConfig,
+    general: Mutex<BucketTable>,
+    allocate: Mutex<BucketTable>,
+    throttled_general: AtomicU64,
+    throttled_allocate: AtomicU64,
+}
+
+impl RateLimiter {
+    fn new(config: RateLimitConfig) -> Self {
+        RateLimiter {
+            config,
+            general: Mutex::new(BucketTable::new()),
+            allocate: Mutex::new(BucketTable::new()),
+            throttled_general: AtomicU64::new(0),
+            throttled_allocate: AtomicU64::new(0),
+        }
+    }
+
+    /// Returns `true` if a datagram from `addr` may proceed, `false` if it
+    /// should be dropped (or, for Allocate requests, answered with a 486).
+    async fn allow(&self, addr: SocketAddr, is_allocate: bool) -> bool {
+        let (table, rate, burst, throttled) = if is_allocate {
+            (
+                &self.allocate,
+                self.config.allocate_rate,
+                self.config.allocate_burst,
+                &self.throttled_allocate,
+            )
+        } else {
+            (
+                &self.general,
+                self.config.general_rate,
+                self.config.general_burst,
+                &self.throttled_general,
+            )
+        };
+
+        let mut table = table.lock().await;
+
+        // Only walk the whole map once per `bucket_ttl`, instead of on every
+        // single packet: under a flood from many distinct source addresses
+        // that's a full-table scan per packet, which amplifies the exact
+        // abuse this rate limiter is meant to mitigate.
+        let now = Instant::now();
+        if now.saturating_duration_since(table.last_pruned) >= self.config.bucket_ttl {
+            self.prune(&mut table.buckets, now);
+            table.last_pruned = now;
+        }
+
+        let bucket = table
+            .buckets
+            .entry(addr)
+            .or_insert_with(|| TokenBucket::new(burst));
+        let allowed = bucket.try_consume(rate, burst);
+        if !allowed {
+            throttled.fetch_add(1, Ordering::Relaxed);
+        }
+
+        allowed
+    }
+
+    /// Drops buckets that haven't been touched within `bucket_ttl`, so an
+    /// attacker sweeping through source addresses can't grow this map
+    /// without bound.
+    fn prune(&self, buckets: &mut HashMap<SocketAddr, TokenBucket>, now: Instant) {
+        let ttl = self.config.bucket_ttl;
+        buckets.retain(|_, bucket| now.saturating_duration_since(bucket.last_refill) < ttl);
+    }
+
+    fn stats(&self) -> RateLimitStats {
+        RateLimitStats {
+            throttled_general: self.throttled_general.load(Ordering::Relaxed),
+            throttled_allocate: self.throttled_allocate.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Returns `true` if `buf` looks like a STUN Allocate request: the 16-bit
+/// STUN message type at bytes 0-1 equal to method `0x003` with the request
+/// class (RFC 5389 section 6).
+fn is_allocate_request(buf: &[u8]) -> bool {
+    buf.len() >= 2 && buf[0] == 0x00 && buf[1] == 0x03
+}
+
+/// Builds a minimal STUN error response (header + a single ERROR-CODE
+/// attribute) carrying `code`, copying the magic cookie and transaction ID
+/// from `req`. Used to answer throttled Allocate requests with a 486
+/// without going through the full request-handling pipeline.
+fn build_stun_error_response(req: &[u8], code: u16, reason: &str) -> Option<Vec<u8>> {
+    if req.len() < 20 {
+        return None;
+    }
+
+    let method = u16::from_be_bytes([req[0], req[1]]);
+    // Error responses set the class bits (c1 at bit 8, c0 at bit 4) to 1.
+    let error_type = (method & 0x3eef) | 0x0110;
+
+    let reason = reason.as_bytes();
+    let value_len = 4 + reason.len();
+    let padded_len = (value_len + 3) / 4 * 4;
+
+    let mut attr = Vec::with_capacity(4 + padded_len);
+    attr.extend_from_slice(&[0x00, 0x09]); // ERROR-CODE attribute type
+    attr.extend_from_slice(&(value_len as u16).to_be_bytes());
+    attr.extend_from_slice(&[0, 0, (code / 100) as u8, (code % 100) as u8]);
+    attr.extend_from_slice(reason);
+    attr.resize(4 + padded_len, 0);
+
+    let mut resp = Vec::with_capacity(20 + attr.len());
+    resp.extend_from_slice(&error_type.to_be_bytes());
+    resp.extend_from_slice(&(attr.len() as u16).to_be_bytes());
+    resp.extend_from_slice(&req[4..20]); // magic cookie + transaction ID
+    resp.extend_from_slice(&attr);
+
+    Some(resp)
+}
+
+/// Minimal abstraction over a reliable, ordered byte stream a TURN client
+/// connects over: a plain `tokio::net::TcpStream`, or a TLS stream layered
+/// on top of one. Blanket-implemented for anything that already satisfies
+/// the bounds, so neither transport needs its own glue type.
+pub trait Stream: AsyncRead + AsyncWrite + Send + Unpin {}
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> Stream for T {}
+
+/// A listener that accepts new [`Stream`] connections, abstracting over
+/// plain TCP and TLS-wrapped TCP the same way [`Stream`] abstracts over the
+/// resulting connections.
+#[async_trait::async_trait]
+pub trait StreamListener: Send + Sync {
+    async fn accept(&self) -> std::io::Result<(Box<dyn Stream>, SocketAddr)>;
+    fn local_addr(&self) -> std::io::Result<SocketAddr>;
+}
+
+/// [`StreamListener`] over a plain `tokio::net::TcpListener`.
+pub struct TcpStreamListener {
+    inner: tokio::net::TcpListener,
+}
+
+impl TcpStreamListener {
+    pub fn new(inner: tokio::net::TcpListener) -> Self {
+        TcpStreamListener { inner }
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamListener for TcpStreamListener {
+    async fn accept(&self) -> std::io::Result<(Box<dyn Stream>, SocketAddr)> {
+        let (stream, addr) = self.inner.accept().await?;
+        Ok((Box::new(stream), addr))
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+/// [`StreamListener`] that terminates TLS on top of each accepted TCP
+/// connection before handing it to the framing reader.
+pub struct TlsStreamListener {
+    inner: tokio::net::TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+}
+
+impl TlsStreamListener {
+    pub fn new(inner: tokio::net::TcpListener, acceptor: tokio_rustls::TlsAcceptor) -> Self {
+        TlsStreamListener { inner, acceptor }
+    }
+}
+
+#[async_trait::async_trait]
+impl StreamListener for TlsStreamListener {
+    async fn accept(&self) -> std::io::Result<(Box<dyn Stream>, SocketAddr)> {
+        let (tcp, addr) = self.inner.accept().await?;
+        let tls = self.acceptor.accept(tcp).await?;
+        Ok((Box::new(tls), addr))
+    }
+
+    fn local_addr(&self) -> std::io::Result<SocketAddr> {
+        self.inner.local_addr()
+    }
+}
+
+/// Reassembles complete STUN messages and ChannelData frames out of a byte
+/// stream, per the RFC 5766 section 11 framing rules for reliable
+/// transports: a STUN message's total size is `20 + length` (the 16-bit
+/// length field at bytes 2-3), while a ChannelData frame's is `4 + length`
+/// (same field position), padded up to a 4-byte boundary on stream
+/// transports.
+struct FrameReader {
+    read_half: tokio::io::ReadHalf<Box<dyn Stream>>,
+    buf: Vec<u8>,
+}
+
+impl FrameReader {
+    fn new(read_half: tokio::io::ReadHalf<Box<dyn Stream>>) -> Self {
+        FrameReader {
+            read_half,
+            buf: Vec::new(),
+        }
+    }
+
+    /// Returns the total on-wire length of the message at the front of
+    /// `buf`, or `None` if not enough bytes have arrived yet to know it.
+    fn framed_len(&self) -> Option<usize> {
+        if self.buf.len() < 2 {
+            return None;
+        }
+
+        let channel_number = u16::from_be_bytes([self.buf[0], self.buf[1]]);
+        if channel_number >= 0x4000 {
+            if self.buf.len() < 4 {
+                return None;
+            }
+            let data_len = u16::from_be_bytes([self.buf[2], self.buf[3]]) as usize;
+            let padded_len = (data_len + 3) / 4 * 4;
+            return Some(4 + padded_len);
+        }
+
+        if self.buf.len() < 20 {
+            return None;
+        }
+        let body_len = u16::from_be_bytes([self.buf[2], self.buf[3]]) as usize;
+        Some(20 + body_len)
+    }
+
+    /// Reads from the stream until one full message is buffered, returns
+    /// it, and drops it from the internal buffer. Returns `Ok(None)` on a
+    /// clean EOF with no partial message left behind, and an
+    /// `UnexpectedEof` error if the stream ends mid-message.
+    async fn read_message(&mut self) -> std::io::Result<Option<Vec<u8>>> {
+        loop {
+            if let Some(len) = self.framed_len() {
+                if self.buf.len() >= len {
+                    return Ok(Some(self.buf.drain(..len).collect()));
+                }
+            }
+
+            let mut chunk = [0u8; INBOUND_MTU];
+            let n = self.read_half.read(&mut chunk).await?;
+            if n == 0 {
+                if self.buf.is_empty() {
+                    return Ok(None);
+                }
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::UnexpectedEof,
+                    "stream closed in the middle of a message",
+                ));
+            }
+            self.buf.extend_from_slice(&chunk[..n]);
+        }
+    }
+}
+
+/// Adapts one accepted stream-transport connection to the existing
+/// [`util::Conn`] abstraction, so responses built by
+/// [`Request::handle_request`] can be written back to it the same way they
+/// are sent over a datagram listener.
+struct StreamConn {
+    write_half: Mutex<tokio::io::WriteHalf<Box<dyn Stream>>>,
+    local_addr: SocketAddr,
+    peer_addr: SocketAddr,
+}
+
+#[async_trait::async_trait]
+impl Conn for StreamConn {
+    async fn connect(&self, _addr: SocketAddr) -> Result<()> {
+        Err(Error::ErrClosed)
+    }
+
+    async fn recv(&self, _buf: &mut [u8]) -> Result<usize> {
+        Err(Error::ErrClosed)
+    }
+
+    async fn recv_from(&self, _buf: &mut [u8]) -> Result<(usize, SocketAddr)> {
+        // Framing is handled by `FrameReader` directly against the read
+        // half, which `Server::stream_conn_loop` owns separately from this
+        // `Conn`; nothing drives a receive through this path.
+        Err(Error::ErrClosed)
+    }
+
+    async fn send(&self, buf: &[u8]) -> Result<usize> {
+        self.send_to(buf, self.peer_addr).await
+    }
+
+    async fn send_to(&self, buf: &[u8], _target: SocketAddr) -> Result<usize> {
+        let mut write_half = self.write_half.lock().await;
+        write_half
+            .write_all(buf)
+            .await
+            .map_err(|_| Error::ErrClosed)?;
+        Ok(buf.len())
+    }
+
+    async fn local_addr(&self) -> Result<SocketAddr> {
+        Ok(self.local_addr)
+    }
+
+    async fn remote_addr(&self) -> Option<SocketAddr> {
+        Some(self.peer_addr)
+    }
+
+    async fn close(&self) -> Result<()> {
+        let mut write_half = self.write_half.lock().await;
+        write_half.shutdown().await.map_err(|_| Error::ErrClosed)
+    }
+
+    fn as_any(&self) -> &(dyn std::any::Any + Send + Sync) {
+        self
+    }
+}
+
 /// The protocol to communicate between the [`Server`]'s public methods
 /// and the threads spawned in the [`read_loop`] method.
 #[derive(Clone)]
@@ -38,8 +537,39 @@ enum Command {
 
     GetMetrics(FiveTuple, Arc<mpsc::Sender<Result<usize>>>),
 
+    /// Command to gracefully drain the [`Server`]: stop accepting new
+    /// datagrams, but keep the allocation manager alive and let
+    /// already-accepted requests (and the allocations they created) run
+    /// until `deadline` elapses.
+    Drain(Instant, Arc<mpsc::Receiver<()>>),
+
     /// Command to close the [`Server`].
     Close(Arc<mpsc::Receiver<()>>),
+
+    /// Command to stop one specific listener added via
+    /// [`Server::add_listener`] (or present since [`Server::new`]),
+    /// identified by its [`ListenerId`]. Every listener's loop receives
+    /// this broadcast and ignores it unless the id is its own.
+    RemoveListener(ListenerId, Arc<mpsc::Receiver<()>>),
+}
+
+/// Shutdown state [`Server::accept_loop`] hands each [`Server::stream_conn_loop`]
+/// it spawns, via a [`watch`] channel rather than a fresh subscription on the
+/// [`Command`] broadcast channel: a `stream_conn_loop` can be spawned in the
+/// same instant a `Drain`/`Close`/`RemoveListener` is broadcast, and a freshly
+/// subscribed `broadcast::Receiver` only observes values sent after it
+/// subscribes, so it could miss that exact command and never learn the
+/// listener is shutting down. A `watch::Receiver` always reflects the latest
+/// value regardless of when it was created, which closes that race.
+#[derive(Debug, Clone, Copy)]
+enum ConnShutdown {
+    /// Keep reading and handling messages as normal.
+    Running,
+    /// Stop accepting new messages, but let in-flight ones finish until
+    /// `deadline`.
+    Draining(Instant),
+    /// Stop immediately.
+    Closed,
 }
 
 /// Server is an instance of the TURN Server
@@ -49,6 +579,9 @@ pub struct Server {
     channel_bind_timeout: Duration,
     pub(crate) nonces: Arc<Mutex<HashMap<String, Instant>>>,
     handle: Mutex<Option<broadcast::Sender<Command>>>,
+    next_listener_id: AtomicU64,
+    listeners: Mutex<Vec<Arc<ListenerHealth>>>,
+    rate_limiter: Arc<RateLimiter>,
 }
 
 impl Server {
@@ -57,12 +590,16 @@ impl Server {
         config.validate()?;
 
         let (handle, _) = broadcast::channel(16);
+        let rate_limiter = Arc::new(RateLimiter::new(config.rate_limit.unwrap_or_default()));
         let mut s = Server {
             auth_handler: config.auth_handler,
             realm: config.realm,
             channel_bind_timeout: config.channel_bind_timeout,
             nonces: Arc::new(Mutex::new(HashMap::new())),
             handle: Mutex::new(Some(handle.clone())),
+            next_listener_id: AtomicU64::new(0),
+            listeners: Mutex::new(Vec::new()),
+            rate_limiter,
         };
 
         if s.channel_bind_timeout == Duration::from_secs(0) {
@@ -74,35 +611,134 @@ impl Server {
             let auth_handler = Arc::clone(&s.auth_handler);
             let realm = s.realm.clone();
             let channel_bind_timeout = s.channel_bind_timeout;
-            let handle_rx = handle.subscribe();
             let conn = p.conn;
             let allocation_manager = Arc::new(Manager::new(ManagerConfig {
                 relay_addr_generator: p.relay_addr_generator,
                 gather_metrics: p.gather_metrics,
             }));
 
-            tokio::spawn({
-                let allocation_manager = Arc::clone(&allocation_manager);
+            let id = s.next_listener_id.fetch_add(1, Ordering::Relaxed);
+            let health = Arc::new(ListenerHealth {
+                id,
+                alive: std::sync::atomic::AtomicBool::new(true),
+                restart_count: AtomicU32::new(0),
+            });
+            s.listeners.lock().await.push(Arc::clone(&health));
 
-                async move {
-                    Server::read_loop(
-                        conn,
-                        allocation_manager,
-                        nonces,
-                        auth_handler,
-                        realm,
-                        channel_bind_timeout,
-                        handle_rx,
-                    )
-                    .await;
-                }
+            tokio::spawn(Server::supervise_read_loop(
+                health,
+                conn,
+                allocation_manager,
+                nonces,
+                auth_handler,
+                realm,
+                channel_bind_timeout,
+                handle.clone(),
+                Arc::clone(&s.rate_limiter),
+            ));
+        }
+
+        for listener in config.stream_listeners.into_iter() {
+            let nonces = Arc::clone(&s.nonces);
+            let auth_handler = Arc::clone(&s.auth_handler);
+            let realm = s.realm.clone();
+            let channel_bind_timeout = s.channel_bind_timeout;
+            let allocation_manager = Arc::new(Manager::new(ManagerConfig {
+                relay_addr_generator: listener.relay_addr_generator,
+                gather_metrics: listener.gather_metrics,
+            }));
+
+            let id = s.next_listener_id.fetch_add(1, Ordering::Relaxed);
+            let health = Arc::new(ListenerHealth {
+                id,
+                alive: std::sync::atomic::AtomicBool::new(true),
+                restart_count: AtomicU32::new(0),
             });
+            s.listeners.lock().await.push(Arc::clone(&health));
+
+            tokio::spawn(Server::supervise_accept_loop(
+                health,
+                listener.listener,
+                allocation_manager,
+                nonces,
+                auth_handler,
+                realm,
+                channel_bind_timeout,
+                handle.clone(),
+                Arc::clone(&s.rate_limiter),
+            ));
         }
 
         Ok(s)
     }
 
+    /// Owns a single listener's read loop for its whole lifetime: spawns it,
+    /// waits for it to exit, and restarts it with capped exponential
+    /// backoff when it exited on a transient error rather than a deliberate
+    /// close, reusing the same allocation manager, nonces and auth handler
+    /// so in-flight allocations aren't lost across a restart.
+    async fn supervise_read_loop(
+        health: Arc<ListenerHealth>,
+        conn: Arc<dyn Conn + Send + Sync>,
+        allocation_manager: Arc<Manager>,
+        nonces: Arc<Mutex<HashMap<String, Instant>>>,
+        auth_handler: Arc<dyn AuthHandler + Send + Sync>,
+        realm: String,
+        channel_bind_timeout: Duration,
+        handle: broadcast::Sender<Command>,
+        rate_limiter: Arc<RateLimiter>,
+    ) {
+        let mut backoff = MIN_LISTENER_RESTART_BACKOFF;
+        let id = health.id;
+
+        loop {
+            health.alive.store(true, Ordering::SeqCst);
+
+            let join = tokio::spawn(Server::read_loop(
+                id,
+                Arc::clone(&conn),
+                Arc::clone(&allocation_manager),
+                Arc::clone(&nonces),
+                Arc::clone(&auth_handler),
+                realm.clone(),
+                channel_bind_timeout,
+                handle.subscribe(),
+                Arc::clone(&rate_limiter),
+            ));
+
+            let exit = join.await;
+            health.alive.store(false, Ordering::SeqCst);
+
+            let should_restart = match exit {
+                Ok(ReadLoopExit::Closed) => false,
+                Ok(ReadLoopExit::Error) => true,
+                Ok(ReadLoopExit::FatalError) => {
+                    log::error!("listener {id} read loop hit a fatal error, not restarting");
+                    false
+                }
+                Err(join_err) => {
+                    log::error!("listener {id} read loop task panicked: {join_err}");
+                    true
+                }
+            };
+
+            if !should_restart {
+                log::debug!("listener {id} stopped, no restart needed");
+                break;
+            }
+
+            let restarts = health.restart_count.fetch_add(1, Ordering::SeqCst) + 1;
+            log::warn!(
+                "listener {id} read loop exited on error, restarting (attempt {restarts}) in {backoff:?}"
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = next_backoff(backoff);
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn read_loop(
+        listener_id: ListenerId,
         conn: Arc<dyn Conn + Send + Sync>,
         allocation_manager: Arc<Manager>,
         nonces: Arc<Mutex<HashMap<String, Instant>>>,
@@ -110,19 +746,48 @@ impl Server {
         realm: String,
         channel_bind_timeout: Duration,
         mut handle_rx: broadcast::Receiver<Command>,
-    ) {
+        rate_limiter: Arc<RateLimiter>,
+    ) -> ReadLoopExit {
         let mut buf = vec![0u8; INBOUND_MTU];
-        loop {
+        // Once set, the loop stops pulling new datagrams off the wire and
+        // only keeps running already-accepted requests (and the allocations
+        // they own) alive until this deadline passes.
+        let mut draining_deadline: Option<Instant> = None;
+        // Kept alive until after `allocation_manager.close()`/`conn.close()`
+        // below, so `Server::graceful_close`'s `closed_tx.closed().await`
+        // only resolves once this loop has actually finished draining and
+        // cleaning up, not as soon as the `Drain` command is received.
+        let mut drain_ack: Option<Arc<mpsc::Receiver<()>>> = None;
+
+        let exit_reason = loop {
+            let recv_fut = if draining_deadline.is_none() {
+                conn.recv_from(&mut buf).left_future()
+            } else {
+                future::pending().right_future()
+            };
+            let drain_deadline_fut = if let Some(deadline) = draining_deadline {
+                tokio::time::sleep_until(deadline).left_future()
+            } else {
+                future::pending().right_future()
+            };
+
             let (n, addr) = futures::select! {
-                v = conn.recv_from(&mut buf).fuse() => {
+                v = recv_fut.fuse() => {
                     match v {
                         Ok(v) => v,
                         Err(err) => {
                             log::debug!("exit read loop on error: {err}");
-                            break;
+                            if is_fatal_io_error(&err) {
+                                break ReadLoopExit::FatalError;
+                            }
+                            break ReadLoopExit::Error;
                         }
                     }
                 },
+                _ = drain_deadline_fut.fuse() => {
+                    log::debug!("graceful drain deadline reached, closing read loop");
+                    break ReadLoopExit::Closed;
+                },
                 cmd = handle_rx.recv().fuse() => {
                     match cmd {
                         Ok(Command::DeleteAllocations(name, _)) => {
@@ -141,7 +806,23 @@ impl Server {
 
                             continue
                         },
-                        Err(RecvError::Closed) | Ok(Command::Close(_)) => break,
+                        Ok(Command::Drain(deadline, closed_rx)) => {
+                            log::debug!("draining read loop, no longer accepting new datagrams");
+                            draining_deadline = Some(deadline);
+                            drain_ack = Some(closed_rx);
+                            continue
+                        },
+                        Ok(Command::RemoveListener(target_id, closed_rx)) if target_id == listener_id => {
+                            log::debug!("listener {listener_id} removed at runtime");
+                            drain_ack = Some(closed_rx);
+                            break ReadLoopExit::Closed;
+                        },
+                        Ok(Command::RemoveListener(_, _)) => continue,
+                        Ok(Command::Close(closed_rx)) => {
+                            drain_ack = Some(closed_rx);
+                            break ReadLoopExit::Closed;
+                        },
+                        Err(RecvError::Closed) => break ReadLoopExit::Closed,
                         Err(RecvError::Lagged(n)) => {
                             log::error!("Turn server has lagged by {n} messages");
                             continue
@@ -150,6 +831,22 @@ impl Server {
                 }
             };
 
+            let is_allocate = is_allocate_request(&buf[..n]);
+            if !rate_limiter.allow(addr, is_allocate).await {
+                if is_allocate {
+                    if let Some(resp) = build_stun_error_response(
+                        &buf[..n],
+                        ERR_ALLOCATION_QUOTA_REACHED,
+                        "Allocation Quota Reached",
+                    ) {
+                        if let Err(err) = conn.send_to(&resp, addr).await {
+                            log::debug!("failed to send quota error to {addr}: {err}");
+                        }
+                    }
+                }
+                continue;
+            }
+
             let mut r = Request {
                 conn: Arc::clone(&conn),
                 src_addr: addr,
@@ -164,9 +861,323 @@ impl Server {
             if let Err(err) = r.handle_request().await {
                 log::error!("error when handling datagram: {}", err);
             }
+        };
+
+        // A transient-error exit is restarted by `supervise_read_loop` on the
+        // same `conn` and `allocation_manager`, so only tear them down when
+        // the listener is stopping for good.
+        if !matches!(exit_reason, ReadLoopExit::Error) {
+            let _ = allocation_manager.close().await;
+            let _ = conn.close().await;
         }
+        drop(drain_ack);
+
+        exit_reason
+    }
+
+    /// Owns a stream (TCP/TLS) listener's accept loop for its whole
+    /// lifetime, restarting it with the same capped exponential backoff as
+    /// [`Server::supervise_read_loop`] if it exits on a transient error.
+    #[allow(clippy::too_many_arguments)]
+    async fn supervise_accept_loop(
+        health: Arc<ListenerHealth>,
+        listener: Box<dyn StreamListener>,
+        allocation_manager: Arc<Manager>,
+        nonces: Arc<Mutex<HashMap<String, Instant>>>,
+        auth_handler: Arc<dyn AuthHandler + Send + Sync>,
+        realm: String,
+        channel_bind_timeout: Duration,
+        handle: broadcast::Sender<Command>,
+        rate_limiter: Arc<RateLimiter>,
+    ) {
+        let mut backoff = MIN_LISTENER_RESTART_BACKOFF;
+        let id = health.id;
+        let listener = Arc::new(listener);
+
+        loop {
+            health.alive.store(true, Ordering::SeqCst);
 
+            let exit = Server::accept_loop(
+                id,
+                Arc::clone(&listener),
+                Arc::clone(&allocation_manager),
+                Arc::clone(&nonces),
+                Arc::clone(&auth_handler),
+                realm.clone(),
+                channel_bind_timeout,
+                handle.subscribe(),
+                Arc::clone(&rate_limiter),
+            )
+            .await;
+
+            health.alive.store(false, Ordering::SeqCst);
+
+            if matches!(exit, ReadLoopExit::Closed) {
+                log::debug!("listener {id} stopped, no restart needed");
+                break;
+            }
+            if matches!(exit, ReadLoopExit::FatalError) {
+                log::error!("listener {id} accept loop hit a fatal error, not restarting");
+                break;
+            }
+
+            let restarts = health.restart_count.fetch_add(1, Ordering::SeqCst) + 1;
+            log::warn!(
+                "listener {id} accept loop exited on error, restarting (attempt {restarts}) in {backoff:?}"
+            );
+            tokio::time::sleep(backoff).await;
+            backoff = next_backoff(backoff);
+        }
+    }
+
+    /// Accepts stream connections until `listener` errors, spawning one
+    /// `stream_conn_loop` task per accepted connection so a slow or
+    /// misbehaving client can't hold up other connections on the same
+    /// listener.
+    #[allow(clippy::too_many_arguments)]
+    async fn accept_loop(
+        listener_id: ListenerId,
+        listener: Arc<Box<dyn StreamListener>>,
+        allocation_manager: Arc<Manager>,
+        nonces: Arc<Mutex<HashMap<String, Instant>>>,
+        auth_handler: Arc<dyn AuthHandler + Send + Sync>,
+        realm: String,
+        channel_bind_timeout: Duration,
+        mut handle_rx: broadcast::Receiver<Command>,
+        rate_limiter: Arc<RateLimiter>,
+    ) -> ReadLoopExit {
+        let local_addr = match listener.local_addr() {
+            Ok(addr) => addr,
+            Err(err) => {
+                log::debug!("exit accept loop, failed to read local address: {err}");
+                return if is_fatal_io_error(&err) {
+                    ReadLoopExit::FatalError
+                } else {
+                    ReadLoopExit::Error
+                };
+            }
+        };
+        let mut draining_deadline: Option<Instant> = None;
+        // Kept alive until after `allocation_manager.close()` below, for the
+        // same reason as `read_loop`'s `drain_ack`.
+        let mut drain_ack: Option<Arc<mpsc::Receiver<()>>> = None;
+        // Joined before `allocation_manager.close()` below so a connection
+        // still mid-`handle_request()` can't have the manager it's using
+        // torn down underneath it. A connection stuck on a blocking write to
+        // an unresponsive peer delays this join same as it already delays
+        // that connection's own drain deadline; nothing new here.
+        let mut conn_handles: Vec<tokio::task::JoinHandle<()>> = Vec::new();
+        // Tells every spawned `stream_conn_loop` when to stop; see
+        // `ConnShutdown` for why this is a `watch` channel and not a fresh
+        // subscription on `handle_rx` per connection.
+        let (shutdown_tx, shutdown_rx) = watch::channel(ConnShutdown::Running);
+
+        let exit_reason = loop {
+            let accept_fut = if draining_deadline.is_none() {
+                listener.accept().left_future()
+            } else {
+                future::pending().right_future()
+            };
+            let drain_deadline_fut = if let Some(deadline) = draining_deadline {
+                tokio::time::sleep_until(deadline).left_future()
+            } else {
+                future::pending().right_future()
+            };
+
+            futures::select! {
+                res = accept_fut.fuse() => {
+                    let (stream, peer_addr) = match res {
+                        Ok(v) => v,
+                        Err(err) => {
+                            log::debug!("exit accept loop on error: {err}");
+                            if is_fatal_io_error(&err) {
+                                break ReadLoopExit::FatalError;
+                            }
+                            break ReadLoopExit::Error;
+                        }
+                    };
+
+                    conn_handles.retain(|h| !h.is_finished());
+                    conn_handles.push(tokio::spawn(Server::stream_conn_loop(
+                        stream,
+                        local_addr,
+                        peer_addr,
+                        Arc::clone(&allocation_manager),
+                        Arc::clone(&nonces),
+                        Arc::clone(&auth_handler),
+                        realm.clone(),
+                        channel_bind_timeout,
+                        shutdown_rx.clone(),
+                        Arc::clone(&rate_limiter),
+                    )));
+                },
+                _ = drain_deadline_fut.fuse() => {
+                    log::debug!("graceful drain deadline reached, closing accept loop {listener_id}");
+                    break ReadLoopExit::Closed;
+                },
+                cmd = handle_rx.recv().fuse() => {
+                    match cmd {
+                        // Answered here, once per listener, rather than in
+                        // every spawned `stream_conn_loop` task: all of a
+                        // listener's connections share the same
+                        // `allocation_manager`, so answering in each of them
+                        // too would report this listener's allocations once
+                        // per open connection instead of once.
+                        Ok(Command::DeleteAllocations(name, _)) => {
+                            allocation_manager
+                                .delete_allocations_by_username(name)
+                                .await;
+                        },
+                        Ok(Command::GetAllocations(sender)) => {
+                            drop(sender.send(allocation_manager.get_allocations().await).await);
+                        },
+                        Ok(Command::GetMetrics(five_tuple, sender)) => {
+                            drop(sender.send(allocation_manager.get_metrics(five_tuple).await).await);
+                        },
+                        Ok(Command::Drain(deadline, closed_rx)) => {
+                            log::debug!("draining accept loop {listener_id}, no longer accepting new connections");
+                            draining_deadline = Some(deadline);
+                            drain_ack = Some(closed_rx);
+                            let _ = shutdown_tx.send(ConnShutdown::Draining(deadline));
+                        },
+                        Ok(Command::RemoveListener(target_id, closed_rx)) if target_id == listener_id => {
+                            log::debug!("listener {listener_id} removed at runtime");
+                            drain_ack = Some(closed_rx);
+                            break ReadLoopExit::Closed;
+                        },
+                        Ok(Command::RemoveListener(_, _)) => {},
+                        Ok(Command::Close(closed_rx)) => {
+                            drain_ack = Some(closed_rx);
+                            break ReadLoopExit::Closed;
+                        },
+                        Err(RecvError::Closed) => break ReadLoopExit::Closed,
+                        Err(RecvError::Lagged(n)) => {
+                            log::error!("Turn server has lagged by {n} messages");
+                        },
+                    }
+                }
+            }
+        };
+
+        // Tell every spawned connection to stop, whatever `exit_reason` was,
+        // before joining them below.
+        let _ = shutdown_tx.send(ConnShutdown::Closed);
+
+        for handle in conn_handles {
+            let _ = handle.await;
+        }
         let _ = allocation_manager.close().await;
+        drop(drain_ack);
+
+        exit_reason
+    }
+
+    /// Services one accepted stream connection: frames complete STUN
+    /// messages / ChannelData off of it and feeds each one into the same
+    /// [`Request`] pipeline datagram listeners use, with the peer's address
+    /// as `src_addr`. Runs until the client disconnects, framing fails, or
+    /// the owning listener is drained/closed/removed; unlike a listening
+    /// socket, a closed connection isn't restarted.
+    #[allow(clippy::too_many_arguments)]
+    async fn stream_conn_loop(
+        stream: Box<dyn Stream>,
+        local_addr: SocketAddr,
+        peer_addr: SocketAddr,
+        allocation_manager: Arc<Manager>,
+        nonces: Arc<Mutex<HashMap<String, Instant>>>,
+        auth_handler: Arc<dyn AuthHandler + Send + Sync>,
+        realm: String,
+        channel_bind_timeout: Duration,
+        mut shutdown_rx: watch::Receiver<ConnShutdown>,
+        rate_limiter: Arc<RateLimiter>,
+    ) {
+        let (read_half, write_half) = tokio::io::split(stream);
+        let conn: Arc<dyn Conn + Send + Sync> = Arc::new(StreamConn {
+            write_half: Mutex::new(write_half),
+            local_addr,
+            peer_addr,
+        });
+        let mut reader = FrameReader::new(read_half);
+
+        'conn: loop {
+            let state = *shutdown_rx.borrow_and_update();
+            if matches!(state, ConnShutdown::Closed) {
+                break 'conn;
+            }
+
+            let read_fut = if matches!(state, ConnShutdown::Running) {
+                reader.read_message().left_future()
+            } else {
+                future::pending().right_future()
+            };
+            let drain_deadline_fut = if let ConnShutdown::Draining(deadline) = state {
+                tokio::time::sleep_until(deadline).left_future()
+            } else {
+                future::pending().right_future()
+            };
+
+            let msg = futures::select! {
+                res = read_fut.fuse() => {
+                    match res {
+                        Ok(Some(msg)) => msg,
+                        Ok(None) => {
+                            log::debug!("stream connection from {peer_addr} closed");
+                            break 'conn;
+                        }
+                        Err(err) => {
+                            log::debug!("exit stream connection loop from {peer_addr} on error: {err}");
+                            break 'conn;
+                        }
+                    }
+                },
+                _ = drain_deadline_fut.fuse() => {
+                    log::debug!("graceful drain deadline reached, closing stream connection from {peer_addr}");
+                    break 'conn;
+                },
+                changed = shutdown_rx.changed().fuse() => {
+                    if changed.is_err() {
+                        // accept_loop's sender was dropped without ever
+                        // signaling ConnShutdown::Closed (e.g. it panicked).
+                        // Stop rather than loop on a state that can never
+                        // change again.
+                        break 'conn;
+                    }
+                    continue 'conn;
+                }
+            };
+
+            let is_allocate = is_allocate_request(&msg);
+            if !rate_limiter.allow(peer_addr, is_allocate).await {
+                if is_allocate {
+                    if let Some(resp) = build_stun_error_response(
+                        &msg,
+                        ERR_ALLOCATION_QUOTA_REACHED,
+                        "Allocation Quota Reached",
+                    ) {
+                        if let Err(err) = conn.send_to(&resp, peer_addr).await {
+                            log::debug!("failed to send quota error to {peer_addr}: {err}");
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let mut r = Request {
+                conn: Arc::clone(&conn),
+                src_addr: peer_addr,
+                buff: msg,
+                allocation_manager: Arc::clone(&allocation_manager),
+                nonces: Arc::clone(&nonces),
+                auth_handler: Arc::clone(&auth_handler),
+                realm: realm.clone(),
+                channel_bind_timeout,
+            };
+
+            if let Err(err) = r.handle_request().await {
+                log::error!("error when handling stream message: {}", err);
+            }
+        }
+
         let _ = conn.close().await;
     }
 
@@ -212,6 +1223,112 @@ impl Server {
         }
     }
 
+    /// Returns a liveness and restart-count snapshot for every registered
+    /// listener (those the server was constructed with plus those added
+    /// via [`Server::add_listener`], minus any removed via
+    /// [`Server::remove_listener`]), in the order they were added. Reading
+    /// this does not depend on a listener's read loop currently being
+    /// alive to answer, since the supervisor updates each entry's health
+    /// directly as loops start, exit and restart.
+    pub async fn listener_status(&self) -> Vec<ListenerStatus> {
+        self.listeners
+            .lock()
+            .await
+            .iter()
+            .map(|health| ListenerStatus {
+                id: health.id,
+                alive: health.alive.load(Ordering::SeqCst),
+                restart_count: health.restart_count.load(Ordering::SeqCst),
+            })
+            .collect()
+    }
+
+    /// Returns how many packets the per-source rate limiter has dropped so
+    /// far, split by whether they were Allocate requests or general
+    /// traffic.
+    pub fn rate_limit_stats(&self) -> RateLimitStats {
+        self.rate_limiter.stats()
+    }
+
+    /// Starts listening on a new datagram connection after construction,
+    /// sharing this server's nonces, auth handler and realm with every
+    /// other listener. Returns the new listener's stable id, which can
+    /// later be passed to [`Server::remove_listener`].
+    pub async fn add_listener(&self, conn_config: ConnConfig) -> Result<ListenerId> {
+        let handle = self.handle.lock().await.clone().ok_or_else(|| Error::ErrClosed)?;
+
+        let nonces = Arc::clone(&self.nonces);
+        let auth_handler = Arc::clone(&self.auth_handler);
+        let realm = self.realm.clone();
+        let channel_bind_timeout = self.channel_bind_timeout;
+        let conn = conn_config.conn;
+        let allocation_manager = Arc::new(Manager::new(ManagerConfig {
+            relay_addr_generator: conn_config.relay_addr_generator,
+            gather_metrics: conn_config.gather_metrics,
+        }));
+
+        let id = self.next_listener_id.fetch_add(1, Ordering::Relaxed);
+        let health = Arc::new(ListenerHealth {
+            id,
+            alive: std::sync::atomic::AtomicBool::new(true),
+            restart_count: AtomicU32::new(0),
+        });
+        self.listeners.lock().await.push(Arc::clone(&health));
+
+        tokio::spawn(Server::supervise_read_loop(
+            health,
+            conn,
+            allocation_manager,
+            nonces,
+            auth_handler,
+            realm,
+            channel_bind_timeout,
+            handle,
+            Arc::clone(&self.rate_limiter),
+        ));
+
+        Ok(id)
+    }
+
+    /// Stops and removes the listener identified by `id`, leaving every
+    /// other listener untouched. Returns once the listener's loop has
+    /// acknowledged the removal.
+    pub async fn remove_listener(&self, id: ListenerId) -> Result<()> {
+        let handle = self.handle.lock().await.clone().ok_or_else(|| Error::ErrClosed)?;
+
+        let (closed_tx, closed_rx) = mpsc::channel(1);
+        handle
+            .send(Command::RemoveListener(id, Arc::new(closed_rx)))
+            .map_err(|_| Error::ErrClosed)?;
+        closed_tx.closed().await;
+
+        self.listeners.lock().await.retain(|health| health.id != id);
+
+        Ok(())
+    }
+
+    /// Gracefully stops the TURN Server: stops accepting new datagrams on
+    /// every listening connection, but leaves already-accepted requests and
+    /// the allocations they created running for up to `timeout` before
+    /// tearing down the allocation manager and the connection itself. Use
+    /// this instead of [`Server::close`] to roll a server without dropping
+    /// active relays.
+    pub async fn graceful_close(&self, timeout: Duration) -> Result<()> {
+        let tx = self.handle.lock().await.take();
+        if let Some(tx) = tx {
+            if tx.receiver_count() == 0 {
+                return Ok(());
+            }
+
+            let (closed_tx, closed_rx) = mpsc::channel(1);
+            let deadline = Instant::now() + timeout;
+            let _ = tx.send(Command::Drain(deadline, Arc::new(closed_rx)));
+            closed_tx.closed().await
+        }
+
+        Ok(())
+    }
+
     /// Close stops the TURN Server. It cleans up any associated state and closes all connections it is managing
     pub async fn close(&self) -> Result<()> {
         let tx = self.handle.lock().await.take();